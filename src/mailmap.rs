@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Canonicalizes author emails using a repository's `.mailmap` (and
+/// optionally an extra mailmap file), so a contributor committing under
+/// several addresses collapses into a single entry.
+pub struct Mailmap {
+    aliases: HashMap<String, String>,
+}
+
+impl Mailmap {
+    /// Loads `extra` (if given) and the repository's own `.mailmap`, later
+    /// entries winning on conflicting aliases.
+    pub fn load(repo_workdir: &Path, extra: Option<&Path>) -> Mailmap {
+        let mut aliases = HashMap::new();
+        if let Some(path) = extra {
+            Mailmap::parse_into(&mut aliases, path);
+        }
+        Mailmap::parse_into(&mut aliases, &repo_workdir.join(".mailmap"));
+        Mailmap { aliases }
+    }
+
+    /// Parses the `<canonical> <alias>` lines of a mailmap file into
+    /// `aliases`, silently skipping a missing or unreadable file.
+    fn parse_into(aliases: &mut HashMap<String, String>, path: &Path) {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(_) => return,
+        };
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // Every email on the line sits between a '<' and the next '>';
+            // the first one is the canonical address, the rest are aliases.
+            let emails: Vec<String> = line
+                .split('<')
+                .skip(1)
+                .filter_map(|part| part.split('>').next())
+                .map(|email| email.to_lowercase())
+                .collect();
+            if let Some(canonical) = emails.first() {
+                for alias in &emails {
+                    aliases.insert(alias.clone(), canonical.clone());
+                }
+            }
+        }
+    }
+
+    /// Returns the canonical email for `email`, or `email` itself (lowered)
+    /// when it has no alias entry.
+    pub fn canonicalize(&self, email: &str) -> String {
+        let email = email.to_lowercase();
+        self.aliases.get(&email).cloned().unwrap_or(email)
+    }
+}
@@ -1,11 +1,13 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
-use chrono::{
-    Date, DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
-};
-use git2::{Config, Error, Repository, Sort};
+use chrono::{Date, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use git2::{Config, Error, Oid, Repository, Sort};
 use structopt::StructOpt;
 
+mod mailmap;
+use mailmap::Mailmap;
+
 #[derive(StructOpt)]
 struct Args {
     #[structopt(name = "user", short = "u")]
@@ -14,28 +16,180 @@ struct Args {
     #[structopt(name = "path", short = "p", default_value = ".")]
     /// the repositories to analyze
     paths: Option<Vec<String>>,
-    #[structopt(name = "weeks", short = "w", default_value = "52")]
-    /// the number of weeks in the past
-    flag_nb_weeks: i64,
+    #[structopt(name = "since", long = "since")]
+    /// start of the date range (YYYY-MM-DD), defaults to one year before `--until`
+    flag_since: Option<String>,
+    #[structopt(name = "until", long = "until")]
+    /// end of the date range (YYYY-MM-DD), defaults to today
+    flag_until: Option<String>,
+    #[structopt(name = "branches", long = "branches")]
+    /// walk the given branches instead of HEAD (may be repeated)
+    flag_branches: Option<Vec<String>>,
+    #[structopt(long = "all")]
+    /// walk every local branch instead of HEAD
+    flag_all: bool,
+    #[structopt(name = "mailmap", long = "mailmap")]
+    /// extra .mailmap file to load in addition to the repository's own
+    flag_mailmap: Option<String>,
+    #[structopt(long = "scale", default_value = "relative")]
+    /// color scale: `relative` to the busiest day, or `absolute` fixed thresholds
+    flag_scale: Scale,
+    #[structopt(long = "color", default_value = "green")]
+    /// color scheme for the squares: green or red
+    flag_color: Color,
+    #[structopt(long = "char", default_value = "🟩")]
+    /// glyph printed for each day's square
+    flag_char: String,
+    #[structopt(long = "tz", default_value = "local")]
+    /// bucket commits by the committer's `local` timezone or by `utc`
+    flag_tz: Tz,
 }
 
-fn print_square(commit_nb: u8) -> () {
-    let color = if commit_nb > 3 {
-        255
-    } else if commit_nb > 2 {
-        251
-    } else if commit_nb > 1 {
-        249
-    } else if commit_nb > 0 {
-        246
-    } else {
-        238
+/// Which timezone a commit's date is bucketed under.
+#[derive(Debug)]
+enum Tz {
+    /// The timezone recorded with the commit's committer time.
+    Local,
+    Utc,
+}
+
+impl std::str::FromStr for Tz {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(Tz::Local),
+            "utc" => Ok(Tz::Utc),
+            _ => Err(format!("invalid tz '{}', expected local or utc", s)),
+        }
+    }
+}
+
+/// RGB color scheme used to paint the squares.
+#[derive(Debug)]
+enum Color {
+    Green,
+    Red,
+}
+
+impl Color {
+    /// The five truecolor shades of this scheme, from emptiest to busiest.
+    fn shades(&self) -> [(u8, u8, u8); 5] {
+        match self {
+            Color::Green => [
+                (22, 62, 42),
+                (0, 109, 50),
+                (38, 166, 65),
+                (57, 211, 83),
+                (86, 255, 117),
+            ],
+            Color::Red => [
+                (62, 22, 22),
+                (109, 0, 0),
+                (166, 38, 38),
+                (211, 57, 57),
+                (255, 86, 86),
+            ],
+        }
+    }
+}
+
+impl std::str::FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "green" => Ok(Color::Green),
+            "red" => Ok(Color::Red),
+            _ => Err(format!("invalid color '{}', expected green or red", s)),
+        }
+    }
+}
+
+/// How a day's commit count maps to one of the five shades in `print_square`.
+#[derive(Debug)]
+enum Scale {
+    /// Shade picked from fixed commit-count thresholds.
+    Absolute,
+    /// Shade picked from the day's count as a fraction of the busiest day.
+    Relative,
+}
+
+impl std::str::FromStr for Scale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "absolute" => Ok(Scale::Absolute),
+            "relative" => Ok(Scale::Relative),
+            _ => Err(format!("invalid scale '{}', expected absolute or relative", s)),
+        }
+    }
+}
+
+/// Push onto the revwalk the refs selected by `--branches`/`--all`, falling
+/// back to HEAD when neither flag is given.
+fn push_refs(
+    repo: &Repository,
+    revwalk: &mut git2::Revwalk,
+    all: bool,
+    branches: Option<&[String]>,
+) -> Result<(), Error> {
+    if all {
+        for reference in repo.references_glob("refs/heads/*")? {
+            revwalk.push(reference?.peel_to_commit()?.id())?;
+        }
+        return Ok(());
+    }
+    if let Some(branches) = branches {
+        for branch in branches {
+            let reference = repo.resolve_reference_from_short_name(branch)?;
+            revwalk.push(reference.peel_to_commit()?.id())?;
+        }
+        return Ok(());
+    }
+    revwalk.push_head()
+}
+
+fn print_square(commit_nb: u8, max_commit_nb: u8, scale: &Scale, color: &Color, glyph: &str) -> () {
+    let bucket = match scale {
+        Scale::Absolute => {
+            if commit_nb > 3 {
+                4
+            } else if commit_nb > 2 {
+                3
+            } else if commit_nb > 1 {
+                2
+            } else if commit_nb > 0 {
+                1
+            } else {
+                0
+            }
+        }
+        Scale::Relative => {
+            if commit_nb == 0 || max_commit_nb == 0 {
+                0
+            } else {
+                (((commit_nb as f64 / max_commit_nb as f64) * 4.0).ceil() as u8).max(1)
+            }
+        }
     };
-    print!("\x1b[38;5;{}m🟩\x1b[0m", color,);
+    let (r, g, b) = color.shades()[bucket as usize];
+    print!("\x1b[38;2;{};{};{}m{}\x1b[0m", r, g, b, glyph);
     ()
 }
 
-fn main() -> Result<(), Error> {
+/// Terminal cell width of a single square's glyph: non-ASCII glyphs (emoji,
+/// the `🟩` default included) render double-width, plain ASCII single-width.
+fn glyph_width(glyph: &str) -> usize {
+    if glyph.chars().all(|c| c.is_ascii()) {
+        1
+    } else {
+        2
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::from_args();
     // Open repository
     let paths: Vec<String> = args.paths.ok_or(vec!["."]).unwrap();
@@ -48,47 +202,94 @@ fn main() -> Result<(), Error> {
         let config = Config::open_default().unwrap();
         return config.get_string("user.email").unwrap();
     });
-    let nb_weeks = args.flag_nb_weeks;
+    // Parse the requested window, defaulting `until` to today and `since` to
+    // one year before `until`. A malformed date surfaces as a readable
+    // error through `?` instead of panicking with an unwrap backtrace.
+    let until_date = match &args.flag_until {
+        Some(s) => NaiveDate::parse_from_str(s, "%Y-%m-%d")?,
+        None => Utc::now().naive_utc().date(),
+    };
+    let since_date = match &args.flag_since {
+        Some(s) => NaiveDate::parse_from_str(s, "%Y-%m-%d")?,
+        None => until_date - Duration::days(365),
+    };
+    let since_time = Utc
+        .from_utc_date(&since_date)
+        .and_time(NaiveTime::from_hms(0, 0, 0))
+        .unwrap();
+    let until_time = Utc
+        .from_utc_date(&until_date)
+        .and_time(NaiveTime::from_hms(23, 59, 59))
+        .unwrap();
+
     // The calendar will contain the count of commit per day
     let mut calendar: HashMap<Date<Utc>, u8> = HashMap::new();
     // Always start on a sunday
-    let start_time = Utc
-        .from_local_datetime(
-            // Get the date of this week's sunday
-            &NaiveDateTime::new(
-                NaiveDate::from_isoywd(
-                    Utc::now().year(),
-                    Utc::now().iso_week().week(),
-                    Weekday::Sun,
-                )
-                // Rewind nb_weeks in the past
-                .checked_sub_signed(Duration::weeks(nb_weeks))
-                .unwrap(),
-                NaiveTime::from_hms(12, 0, 0),
-            ),
-        )
-        .unwrap();
+    let first_day: Date<Utc> = Utc.from_utc_date(
+        &(since_date - Duration::days(since_date.weekday().num_days_from_sunday() as i64)),
+    );
+    // The column holding the week `until_date` falls in
+    let last_week_start: Date<Utc> = Utc.from_utc_date(
+        &(until_date - Duration::days(until_date.weekday().num_days_from_sunday() as i64)),
+    );
+    let nb_weeks = (last_week_start - first_day).num_days() / 7 + 1;
 
     for repo in repos {
         let mut revwalk = repo.revwalk()?;
         revwalk.set_sorting(Sort::NONE | Sort::TIME)?;
-        revwalk.push_head()?;
+        push_refs(
+            &repo,
+            &mut revwalk,
+            args.flag_all,
+            args.flag_branches.as_deref(),
+        )?;
+
+        // Resolve author email aliases through this repository's .mailmap,
+        // and the requested user through the same map so either its
+        // canonical or an aliased email can be passed to `-u`.
+        let workdir = repo.workdir().unwrap_or_else(|| repo.path());
+        let mailmap = Mailmap::load(workdir, args.flag_mailmap.as_ref().map(Path::new));
+        let canonical_user_email = mailmap.canonicalize(&user_email);
+
+        // A commit reachable from several refs must only be counted once.
+        let mut seen_commits: HashSet<Oid> = HashSet::new();
 
         // Walk the commit list
         for r_commit_id in revwalk {
             if let Ok(commit_id) = r_commit_id {
+                if !seen_commits.insert(commit_id) {
+                    continue;
+                }
                 if let Ok(commit) = repo.find_commit(commit_id) {
-                    // If we reache a commit older than our limit, we stop.
-                    if commit.time().seconds() < start_time.timestamp() {
+                    // Commits newer than `until` are simply not in range yet.
+                    if commit.time().seconds() > until_time.timestamp() {
+                        continue;
+                    }
+                    // If we reach a commit older than `since`, we stop.
+                    if commit.time().seconds() < since_time.timestamp() {
                         break;
                     }
-                    if user_email == commit.author().email().ok_or("unknown").unwrap() {
-                        // Get the commit date
-                        let commit_date = DateTime::<Utc>::from_utc(
-                            NaiveDateTime::from_timestamp(commit.time().seconds(), 0),
-                            Utc,
-                        )
-                        .date();
+                    let commit_author_email =
+                        mailmap.canonicalize(commit.author().email().ok_or("unknown").unwrap());
+                    if canonical_user_email == commit_author_email {
+                        // Get the commit date, in the committer's own timezone
+                        // by default so a late-evening commit doesn't shift
+                        // onto the wrong square.
+                        let naive_date = match args.flag_tz {
+                            Tz::Local => {
+                                let offset_seconds =
+                                    commit.time().offset_minutes() as i64 * 60;
+                                NaiveDateTime::from_timestamp(
+                                    commit.time().seconds() + offset_seconds,
+                                    0,
+                                )
+                                .date()
+                            }
+                            Tz::Utc => {
+                                NaiveDateTime::from_timestamp(commit.time().seconds(), 0).date()
+                            }
+                        };
+                        let commit_date: Date<Utc> = Utc.from_utc_date(&naive_date);
                         // Increment the date counter
                         match calendar.get_mut(&commit_date) {
                             Some(v) => {
@@ -104,14 +305,61 @@ fn main() -> Result<(), Error> {
         }
     }
 
-    let first_day: Date<Utc> = start_time.date();
+    // Busiest day, used to scale shades when `--scale relative` is in effect.
+    let max_commit_nb = *calendar.values().max().unwrap_or(&0);
+
+    // Left gutter reserved for the weekday labels, so the grid columns below
+    // line up with the month labels above.
+    const GUTTER_WIDTH: usize = 4;
+
+    // Each grid column is as wide as one square's glyph, so the axes below
+    // must use the same width, not an emoji-only 2-cell assumption.
+    let column_width = glyph_width(&args.flag_char);
+
+    // Top axis: the month abbreviation above the week-column where it
+    // starts. A 3-letter abbreviation may need more than one column's width
+    // to stay legible, so a label borrows as many following columns as it
+    // needs and those columns are skipped. A month that begins under a
+    // borrowed column is labelled at the next column that is not borrowed,
+    // and a label started in the last columns runs past the grid's width.
+    print!("{:width$}", "", width = GUTTER_WIDTH);
+    let mut last_month = 0;
+    let mut i = 0;
+    while i < nb_weeks {
+        let column_start = first_day.checked_add_signed(Duration::days(i * 7)).unwrap();
+        if column_start.month() != last_month {
+            last_month = column_start.month();
+            let label_cols = (3 + column_width - 1) / column_width;
+            print!("{:<width$}", column_start.format("%b"), width = label_cols * column_width);
+            i += label_cols as i64;
+        } else {
+            print!("{:width$}", "", width = column_width);
+            i += 1;
+        }
+    }
+    println!("");
+
+    // Left axis: every other weekday name, to keep the gutter uncluttered.
     for shift in 0..7 {
+        let label = match shift {
+            1 => "Mon",
+            3 => "Wed",
+            5 => "Fri",
+            _ => "",
+        };
+        print!("{:<width$}", label, width = GUTTER_WIDTH);
         for i in 0..nb_weeks {
             let datei = first_day
                 .checked_add_signed(Duration::days(shift + i * 7))
                 .unwrap();
             let count = calendar.get(&datei).unwrap_or(&0);
-            print_square(*count);
+            print_square(
+                *count,
+                max_commit_nb,
+                &args.flag_scale,
+                &args.flag_color,
+                &args.flag_char,
+            );
         }
         println!("");
     }